@@ -1,13 +1,8 @@
 #!cargo r
 
-use rand::Rng;
-use std::{
-    sync::{
-        atomic::{AtomicU16, Ordering::Relaxed},
-        Arc,
-    },
-    thread,
-};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
 
 const SCREEN_WIDTH: usize = 2560 * 1;
 const SCREEN_HEIGHT: usize = 1440 * 1;
@@ -18,19 +13,12 @@ const ITERATIONS_B: usize = 5000;
 
 const POINTS: usize = 1_0_000_000;
 
-const COMPLEX_PLANE_VIEW_WIDTH: f64 = 4.3;
-const COMPLEX_PLANE_VIEW_HEIGHT: f64 =
-    (SCREEN_HEIGHT as f64 / SCREEN_WIDTH as f64) * COMPLEX_PLANE_VIEW_WIDTH;
+/// The view width (in the complex plane) at `zoom == 1.0`, and the default
+/// pan applied when no `--center-re` is given on the command line.
+const DEFAULT_VIEW_WIDTH: f64 = 4.3;
+const DEFAULT_PAN_RIGHT: f64 = 0.5;
 
-const PAN_RIGHT: f64 = 0.5;
-
-const TOP_LEFT: Complex = Complex {
-    re: COMPLEX_PLANE_VIEW_WIDTH / -2.0 - PAN_RIGHT,
-    im: COMPLEX_PLANE_VIEW_HEIGHT / 2.0,
-};
-
-const PIXEL_WIDTH: f64 = COMPLEX_PLANE_VIEW_WIDTH as f64 / SCREEN_WIDTH as f64;
-const PIXEL_HEIGHT: f64 = PIXEL_WIDTH;
+const FILTER_LUT_RESOLUTION: usize = 256;
 
 #[derive(Debug, Copy, Clone)]
 struct Complex {
@@ -38,27 +26,318 @@ struct Complex {
     im: f64,
 }
 
-#[derive(Debug)]
-struct Pixel {
-    x: usize,
-    y: usize,
+/// The rendered region of the complex plane: an axis-aligned bounding box
+/// (`min` bottom-left, `max` top-right), the pixel resolution it's mapped
+/// onto, and an optional rotation applied around its center before
+/// projecting to pixels.
+#[derive(Debug, Copy, Clone)]
+struct Viewport {
+    min: Complex,
+    max: Complex,
+    screen_width: usize,
+    screen_height: usize,
+    rotation: f64,
 }
 
-type BuddhabrotChannel = Vec<Vec<AtomicU16>>;
+impl Viewport {
+    fn new(min: Complex, max: Complex, screen_width: usize, screen_height: usize) -> Viewport {
+        Viewport {
+            min,
+            max,
+            screen_width,
+            screen_height,
+            rotation: 0.0,
+        }
+    }
+
+    /// Builds a viewport centered on `center`, `zoom` times narrower than
+    /// `DEFAULT_VIEW_WIDTH`, keeping the aspect ratio of `screen_width` by
+    /// `screen_height`.
+    fn from_center_zoom(
+        center: Complex,
+        zoom: f64,
+        screen_width: usize,
+        screen_height: usize,
+    ) -> Viewport {
+        let width = DEFAULT_VIEW_WIDTH / zoom;
+        let height = (screen_height as f64 / screen_width as f64) * width;
+
+        Viewport::new(
+            Complex {
+                re: center.re - width / 2.0,
+                im: center.im - height / 2.0,
+            },
+            Complex {
+                re: center.re + width / 2.0,
+                im: center.im + height / 2.0,
+            },
+            screen_width,
+            screen_height,
+        )
+    }
 
-fn get_pixel(c: &Complex) -> Option<Pixel> {
-    if c.re < TOP_LEFT.re
-        || c.re > TOP_LEFT.re + COMPLEX_PLANE_VIEW_WIDTH
-        || c.im > TOP_LEFT.im
-        || c.im < TOP_LEFT.im - COMPLEX_PLANE_VIEW_HEIGHT
-    {
-        return None;
+    /// Returns this viewport rotated by `theta` radians around its center.
+    fn rotated(mut self, theta: f64) -> Viewport {
+        self.rotation = theta;
+        self
     }
 
-    return Some(Pixel {
-        x: ((c.re - TOP_LEFT.re) / PIXEL_WIDTH) as usize,
-        y: ((TOP_LEFT.im - c.im) / PIXEL_HEIGHT) as usize,
-    });
+    fn center(&self) -> Complex {
+        Complex {
+            re: (self.min.re + self.max.re) / 2.0,
+            im: (self.min.im + self.max.im) / 2.0,
+        }
+    }
+
+    fn pixel_width(&self) -> f64 {
+        (self.max.re - self.min.re) / self.screen_width as f64
+    }
+
+    fn pixel_height(&self) -> f64 {
+        (self.max.im - self.min.im) / self.screen_height as f64
+    }
+
+    /// Rotates `c` by `-rotation` around the viewport's center, i.e. maps
+    /// from plane space into this viewport's unrotated local frame.
+    fn to_local_frame(self, c: &Complex) -> Complex {
+        if self.rotation == 0.0 {
+            return *c;
+        }
+
+        let center = self.center();
+        let dx = c.re - center.re;
+        let dy = c.im - center.im;
+        let (sin, cos) = (-self.rotation).sin_cos();
+
+        Complex {
+            re: center.re + dx * cos - dy * sin,
+            im: center.im + dx * sin + dy * cos,
+        }
+    }
+
+    /// Rotates `c` by `+rotation` around the viewport's center, i.e. the
+    /// inverse of `to_local_frame` -- maps from this viewport's unrotated
+    /// local frame back into plane space.
+    #[allow(clippy::wrong_self_convention)]
+    fn from_local_frame(self, c: &Complex) -> Complex {
+        if self.rotation == 0.0 {
+            return *c;
+        }
+
+        let center = self.center();
+        let dx = c.re - center.re;
+        let dy = c.im - center.im;
+        let (sin, cos) = self.rotation.sin_cos();
+
+        Complex {
+            re: center.re + dx * cos - dy * sin,
+            im: center.im + dx * sin + dy * cos,
+        }
+    }
+
+    fn contains(&self, c: &Complex) -> bool {
+        let p = self.to_local_frame(c);
+        p.re >= self.min.re && p.re <= self.max.re && p.im >= self.min.im && p.im <= self.max.im
+    }
+
+    /// Maps a complex-plane point to continuous pixel coordinates, without
+    /// truncating to a single integer pixel, so callers can splat it
+    /// across every pixel within a filter's support radius.
+    fn to_pixel(self, c: &Complex) -> Option<(f64, f64)> {
+        if !self.contains(c) {
+            return None;
+        }
+
+        let p = self.to_local_frame(c);
+
+        Some((
+            (p.re - self.min.re) / self.pixel_width(),
+            (self.max.im - p.im) / self.pixel_height(),
+        ))
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn from_pixel(self, px: f64, py: f64) -> Complex {
+        let local = Complex {
+            re: self.min.re + px * self.pixel_width(),
+            im: self.max.im - py * self.pixel_height(),
+        };
+
+        self.from_local_frame(&local)
+    }
+}
+
+/// A reconstruction filter kernel, in the style of a physically based
+/// renderer's film filter.
+#[derive(Debug, Copy, Clone)]
+enum FilterKind {
+    Box,
+    Gaussian { alpha: f64 },
+    MitchellNetravali { b: f64, c: f64 },
+}
+
+/// A filter kernel plus its support radius (in pixels) and a precomputed
+/// 1-D lookup table indexed by distance, so `weight()` is a table lookup
+/// rather than re-evaluating the kernel per splat.
+struct Filter {
+    radius: f64,
+    lut: Vec<f64>,
+}
+
+impl Filter {
+    fn new(kind: FilterKind, radius: f64) -> Filter {
+        let lut = (0..=FILTER_LUT_RESOLUTION)
+            .map(|i| {
+                let d = radius * (i as f64 / FILTER_LUT_RESOLUTION as f64);
+                Filter::eval(kind, d, radius).max(0.0)
+            })
+            .collect();
+
+        Filter { radius, lut }
+    }
+
+    fn eval(kind: FilterKind, d: f64, radius: f64) -> f64 {
+        match kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Gaussian { alpha } => {
+                (-alpha * d * d).exp() - (-alpha * radius * radius).exp()
+            }
+            FilterKind::MitchellNetravali { b, c } => mitchell_netravali(d, b, c),
+        }
+    }
+
+    /// Default construction for each kernel, with a support radius matched
+    /// to that kernel's usual domain.
+    fn box_filter() -> Filter {
+        Filter::new(FilterKind::Box, 0.5)
+    }
+
+    fn gaussian(alpha: f64) -> Filter {
+        Filter::new(FilterKind::Gaussian { alpha }, 1.5)
+    }
+
+    fn mitchell_netravali(b: f64, c: f64) -> Filter {
+        Filter::new(FilterKind::MitchellNetravali { b, c }, 2.0)
+    }
+
+    /// Builds the `Filter` for a runtime-selected `FilterKind`, picking the
+    /// matching default support radius for each kernel.
+    fn from_kind(kind: FilterKind) -> Filter {
+        match kind {
+            FilterKind::Box => Filter::box_filter(),
+            FilterKind::Gaussian { alpha } => Filter::gaussian(alpha),
+            FilterKind::MitchellNetravali { b, c } => Filter::mitchell_netravali(b, c),
+        }
+    }
+
+    fn weight(&self, distance: f64) -> f64 {
+        if distance >= self.radius {
+            return 0.0;
+        }
+
+        let t = (distance / self.radius) * FILTER_LUT_RESOLUTION as f64;
+        self.lut[t as usize]
+    }
+}
+
+/// The classic two-piece Mitchell-Netravali kernel, defined on `x in [0, 2)`.
+fn mitchell_netravali(x: f64, b: f64, c: f64) -> f64 {
+    let x2 = x * x;
+    let x3 = x2 * x;
+
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x3 + (-18.0 + 12.0 * b + 6.0 * c) * x2 + (6.0 - 2.0 * b))
+            / 6.0
+    } else if x < 2.0 {
+        ((-b - 6.0 * c) * x3
+            + (6.0 * b + 30.0 * c) * x2
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+/// One film pixel: the filter-weighted density accumulated at this pixel.
+/// Each worker accumulates its run of batches into its own private film
+/// shard (see `generate_channel`), so this needs no interior mutability or
+/// atomics -- the shared `AtomicU16` counters this used to be could overflow at
+/// high sample counts; a plain `f64` merged at the end cannot.
+///
+/// This must stay a *sum*, not an average: dividing by the accumulated
+/// filter weight would cancel out the orbit count (every pixel touched by
+/// at least one sample would resolve to the same value regardless of how
+/// many samples landed there), collapsing the density estimate into a
+/// binary silhouette.
+#[derive(Clone)]
+struct Accumulator {
+    density: f64,
+}
+
+impl Accumulator {
+    fn new() -> Accumulator {
+        Accumulator { density: 0.0 }
+    }
+
+    fn add(&mut self, density_delta: f64) {
+        self.density += density_delta;
+    }
+
+    fn resolve(&self) -> f64 {
+        self.density
+    }
+}
+
+type BuddhabrotChannel = Vec<Vec<Accumulator>>;
+
+fn new_channel() -> BuddhabrotChannel {
+    let mut channel: BuddhabrotChannel = Vec::with_capacity(SCREEN_HEIGHT);
+
+    for _ in 0..SCREEN_HEIGHT {
+        let mut row = Vec::with_capacity(SCREEN_WIDTH);
+        for _ in 0..SCREEN_WIDTH {
+            row.push(Accumulator::new());
+        }
+        channel.push(row);
+    }
+
+    channel
+}
+
+/// Adds `src`'s samples into `dst`, pixel by pixel. Used to merge each
+/// worker's private film shard into the final result.
+fn merge_channel(dst: &mut BuddhabrotChannel, src: &BuddhabrotChannel) {
+    for (dst_row, src_row) in dst.iter_mut().zip(src.iter()) {
+        for (dst_acc, src_acc) in dst_row.iter_mut().zip(src_row.iter()) {
+            dst_acc.add(src_acc.density);
+        }
+    }
+}
+
+/// Splats a single orbit point into every pixel within `filter`'s support
+/// radius of its continuous position, weighting each by the filter's
+/// response at that distance times `sample_weight` (the sample's
+/// importance weight; `1.0` for uniform sampling).
+fn splat(channel: &mut BuddhabrotChannel, px: f64, py: f64, filter: &Filter, sample_weight: f64) {
+    let r = filter.radius;
+
+    let x_min = (px - r).floor().max(0.0) as usize;
+    let x_max = (px + r).ceil().min(SCREEN_WIDTH as f64 - 1.0).max(0.0) as usize;
+    let y_min = (py - r).floor().max(0.0) as usize;
+    let y_max = (py + r).ceil().min(SCREEN_HEIGHT as f64 - 1.0).max(0.0) as usize;
+
+    for (y, row) in channel.iter_mut().enumerate().take(y_max + 1).skip(y_min) {
+        for (x, acc) in row.iter_mut().enumerate().take(x_max + 1).skip(x_min) {
+            let dx = px - (x as f64 + 0.5);
+            let dy = py - (y as f64 + 0.5);
+            let w = filter.weight((dx * dx + dy * dy).sqrt());
+
+            if w > 0.0 {
+                acc.add(sample_weight * w);
+            }
+        }
+    }
 }
 
 impl Complex {
@@ -85,181 +364,725 @@ impl Complex {
     }
 }
 
-fn pixels_to_png(
-    r: &BuddhabrotChannel,
-    g: &BuddhabrotChannel,
-    b: &BuddhabrotChannel,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut image = image::ImageBuffer::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+/// An output response curve, in the style of a film renderer's write
+/// stage: maps an exposed, arbitrarily large linear value down to the
+/// `[0, 1]` range a display can show.
+#[derive(Debug, Copy, Clone)]
+enum ResponseCurve {
+    Linear,
+    Gamma(f64),
+    Logarithmic(f64),
+    Reinhard,
+}
+
+/// Computes the value at percentile `p` (0..=100) of `values`, sorting
+/// them in place. Used so a single freakishly hot pixel doesn't become
+/// the white point and crush everything else to black.
+fn percentile(values: &mut [f64], p: f64) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let idx = ((p / 100.0) * (values.len() as f64 - 1.0)).round() as usize;
+    values[idx.min(values.len() - 1)]
+}
+
+/// The configurable part of the output pipeline: per-channel exposure,
+/// a response curve, and an optional percentile (instead of the absolute
+/// max) to use as the white point.
+struct OutputPipeline {
+    exposure: [f64; 3],
+    curve: ResponseCurve,
+    clip_percentile: Option<f64>,
+}
+
+impl OutputPipeline {
+    fn new(
+        exposure: [f64; 3],
+        curve: ResponseCurve,
+        clip_percentile: Option<f64>,
+    ) -> OutputPipeline {
+        OutputPipeline {
+            exposure,
+            curve,
+            clip_percentile,
+        }
+    }
+
+    fn resolve_channel(channel: &BuddhabrotChannel, exposure: f64) -> Vec<f64> {
+        channel
+            .iter()
+            .flat_map(|row| row.iter().map(move |acc| acc.resolve() * exposure))
+            .collect()
+    }
+
+    fn white_point(&self, raw: &[f64]) -> f64 {
+        let white = match self.clip_percentile {
+            Some(p) => percentile(&mut raw.to_vec(), p),
+            None => raw.iter().cloned().fold(0.0, f64::max),
+        };
+
+        white.max(f64::MIN_POSITIVE)
+    }
+
+    fn tone_map_channel(&self, raw: &[f64]) -> Vec<f64> {
+        let white = self.white_point(raw);
+
+        raw.iter()
+            .map(|&v| {
+                match self.curve {
+                    ResponseCurve::Linear => v / white,
+                    ResponseCurve::Gamma(gamma) => (v / white).max(0.0).powf(1.0 / gamma),
+                    ResponseCurve::Logarithmic(k) => (1.0 + k * v).ln() / (1.0 + k * white).ln(),
+                    ResponseCurve::Reinhard => {
+                        let x = v / white;
+                        x / (1.0 + x)
+                    }
+                }
+                .clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+
+    /// Exposes, tone-maps, and white-point normalizes all three channels
+    /// into `[0, 1]`, keeping every intermediate value in `f64`.
+    fn map(
+        &self,
+        r: &BuddhabrotChannel,
+        g: &BuddhabrotChannel,
+        b: &BuddhabrotChannel,
+    ) -> [Vec<f64>; 3] {
+        [
+            self.tone_map_channel(&Self::resolve_channel(r, self.exposure[0])),
+            self.tone_map_channel(&Self::resolve_channel(g, self.exposure[1])),
+            self.tone_map_channel(&Self::resolve_channel(b, self.exposure[2])),
+        ]
+    }
+}
+
+/// Writes both an 8-bit and a 16-bit PNG from the same tone-mapped `[0, 1]`
+/// channels, so faint outer-orbit structure that would round to zero at
+/// 8 bits is still preserved in the 16-bit output.
+fn write_images(mapped: &[Vec<f64>; 3]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut image8 = image::ImageBuffer::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+    let mut image16: image::ImageBuffer<image::Rgb<u16>, Vec<u16>> =
+        image::ImageBuffer::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
 
     for y in 0..SCREEN_HEIGHT {
         for x in 0..SCREEN_WIDTH {
-            image.put_pixel(
+            let idx = y * SCREEN_WIDTH + x;
+            let rgb = [mapped[0][idx], mapped[1][idx], mapped[2][idx]];
+
+            image8.put_pixel(
+                x as u32,
+                y as u32,
+                image::Rgb(rgb.map(|v| (v * 255.0).round() as u8)),
+            );
+            image16.put_pixel(
                 x as u32,
                 y as u32,
-                image::Rgb([
-                    r[y][x].load(Relaxed) as u8,
-                    g[y][x].load(Relaxed) as u8,
-                    b[y][x].load(Relaxed) as u8,
-                ]),
+                image::Rgb(rgb.map(|v| (v * 65535.0).round() as u16)),
             );
         }
     }
 
-    image.save("buddhabrot.png")?;
+    image8.save("buddhabrot.png")?;
+    image16.save("buddhabrot_16bit.png")?;
 
     Ok(())
 }
 
-fn generate(r: &BuddhabrotChannel, g: &BuddhabrotChannel, b: &BuddhabrotChannel) {
-    let mut rng = rand::thread_rng();
+/// Which strategy `generate_channel` uses to pick the seed `c` for each
+/// orbit.
+///
+/// Uniform sampling is unbiased by construction but wastes almost all of
+/// its samples: most `c` values never escape, or escape having visited
+/// almost nothing inside the view rectangle, and this gets dramatically
+/// worse when zoomed in. Metropolis instead builds a Markov chain that
+/// drifts towards brighter (more in-view) seeds, concentrating work where
+/// it pays off. The tradeoff is that successive samples are correlated
+/// rather than i.i.d., so the chain needs a warm-up and a chance of large
+/// uniform jumps (`METROPOLIS_P_LARGE`) to avoid getting stuck in one
+/// bright region. Each accepted sample's orbit is deposited with weight
+/// `mean_brightness / b_curr`: the chain's stationary density is already
+/// proportional to `b(c)`, and depositing the full orbit contributes
+/// another factor of `b(c)`, so dividing by `b_curr` is required to cancel
+/// that and recover the same (uniform) target density that
+/// `SamplingMode::Uniform` estimates -- scaling by the running mean alone
+/// would leave every region weighted by an extra, uncancelled `b(c)`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SamplingMode {
+    Uniform,
+    Metropolis,
+}
+
+const METROPOLIS_MUTATION_SIGMA: f64 = 0.001;
+const METROPOLIS_P_LARGE: f64 = 0.3;
+const METROPOLIS_WARM_UP_SAMPLES: usize = 10_000;
+
+/// Draws a seed uniformly from the viewport's bounding box, rotated by
+/// `+rotation` around its center so the sampled region lines up with the
+/// rendered region (`to_pixel` bins in that same rotated frame) -- sampling
+/// the unrotated box directly would never draw from the corners a rotated
+/// crop actually shows, skewing density near the frame edges.
+fn uniform_sample(rng: &mut impl Rng, viewport: &Viewport) -> Complex {
+    let local = Complex {
+        re: rng.gen::<f64>() * (viewport.max.re - viewport.min.re) + viewport.min.re,
+        im: rng.gen::<f64>() * (viewport.max.im - viewport.min.im) + viewport.min.im,
+    };
+
+    viewport.from_local_frame(&local)
+}
+
+/// Draws a zero-mean Gaussian via Box-Muller, off of the same `Rng` the
+/// rest of the sampler uses (no extra distribution crate needed).
+fn sample_gaussian(rng: &mut impl Rng, sigma: f64) -> f64 {
+    let u1: f64 = rng.gen::<f64>().max(f64::MIN_POSITIVE);
+    let u2: f64 = rng.gen::<f64>();
+
+    sigma * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Iterates `c` until it escapes (returning the visited orbit) or the
+/// iteration cap is hit (returning `None`).
+fn escape_orbit(c: &Complex) -> Option<Vec<Complex>> {
+    let mut visited = Vec::with_capacity(ITERATIONS_R);
+    let mut z = Complex { re: 0.0, im: 0.0 };
 
-    // Create a two dimensional array of pixels
+    for _ in 0..ITERATIONS_R {
+        z = z.square().add(c);
+        visited.push(z);
 
-    for i in 0..POINTS {
-        if i % (1024 * 128) == 0 {
-            println!("{:.2}% Done", (i as f64 / POINTS as f64) * 100.0);
+        if z.abssq() > 4.0 {
+            return Some(visited);
         }
+    }
 
-        // Generate a random complex number
-        let c = Complex {
-            re: rng.gen::<f64>() * COMPLEX_PLANE_VIEW_WIDTH as f64 + TOP_LEFT.re,
-            im: TOP_LEFT.im - rng.gen::<f64>() * COMPLEX_PLANE_VIEW_HEIGHT as f64,
-        };
+    None
+}
 
-        let mut visited = Vec::with_capacity(ITERATIONS_R);
+/// Iteration cap for domain coloring. Much shallower than the Buddhabrot's
+/// `ITERATIONS_R`/`G`/`B`, since domain coloring only needs enough
+/// iterations to separate escape bands visually, not to build orbit
+/// statistics.
+const DOMAIN_COLOR_MAX_ITERATIONS: usize = 500;
 
-        let mut z = Complex { re: 0.0, im: 0.0 };
+/// Iterates `c` until escape or `max_iterations`, returning `z`'s final
+/// argument together with a smooth (fractional) escape count, or `None` if
+/// `c` never escaped within the cap.
+fn domain_color_escape(c: &Complex, max_iterations: usize) -> Option<(f64, f64)> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
 
-        let mut should_green = true;
-        let mut should_blue = true;
+    for i in 0..max_iterations {
+        z = z.square().add(c);
 
-        for i in 0..ITERATIONS_R {
-            if i > ITERATIONS_G {
-                should_green = false;
-            }
+        let abssq = z.abssq();
+        if abssq > 4.0 {
+            // Standard continuous (smooth) escape count, so the iteration
+            // bands that a bare integer count would produce disappear.
+            let nu = (abssq.ln() / 2.0 / 2f64.ln()).ln() / 2f64.ln();
+            return Some((z.im.atan2(z.re), i as f64 + 1.0 - nu));
+        }
+    }
 
-            if i > ITERATIONS_B {
-                should_blue = false;
-            }
+    None
+}
 
-            // Calculate the next complex number
-            z = z.square().add(&c);
+/// Converts an HSV color to RGB via the standard sextant-selection formula:
+/// `h` in degrees (`[0, 360)`), `s` and `v` in `[0, 1]`.
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> (u8, u8, u8) {
+    let c = v * s;
+    let h_prime = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h_prime % 2.0 - 1.0).abs());
+    let m = v - c;
 
-            visited.push(z);
+    let (r1, g1, b1) = if h_prime < 1.0 {
+        (c, x, 0.0)
+    } else if h_prime < 2.0 {
+        (x, c, 0.0)
+    } else if h_prime < 3.0 {
+        (0.0, c, x)
+    } else if h_prime < 4.0 {
+        (0.0, x, c)
+    } else if h_prime < 5.0 {
+        (x, 0.0, c)
+    } else {
+        (c, 0.0, x)
+    };
 
-            if z.abssq() > 4.0 {
-                for v in visited.iter() {
-                    let pixel = get_pixel(&v);
+    (
+        ((r1 + m) * 255.0).round() as u8,
+        ((g1 + m) * 255.0).round() as u8,
+        ((b1 + m) * 255.0).round() as u8,
+    )
+}
 
-                    if let Some(pixel) = pixel {
-                        r[pixel.y][pixel.x].fetch_add(1, Relaxed);
-                    }
-                }
+/// Renders the domain-coloring companion view: for every screen pixel, maps
+/// it to a seed `c` via the viewport, iterates it to escape, and colors the
+/// pixel by `arg(z)` (hue) and the smooth escape count (value) -- reusing
+/// `Complex` math and the `Viewport` pixel mapping, but bypassing orbit
+/// splatting entirely. Seeds that never escape (presumed in-set) render
+/// black.
+fn render_domain_color(viewport: Viewport, max_iterations: usize) -> image::RgbImage {
+    let mut image =
+        image::RgbImage::new(viewport.screen_width as u32, viewport.screen_height as u32);
 
-                if should_green {
-                    for v in visited.iter().take(ITERATIONS_G) {
-                        let pixel = get_pixel(&v);
+    for y in 0..viewport.screen_height {
+        for x in 0..viewport.screen_width {
+            let c = viewport.from_pixel(x as f64 + 0.5, y as f64 + 0.5);
 
-                        if let Some(pixel) = pixel {
-                            g[pixel.y][pixel.x].fetch_add(1, Relaxed);
-                        }
-                    }
+            let (r, g, b) = match domain_color_escape(&c, max_iterations) {
+                Some((arg, smooth)) => {
+                    let value = (smooth / max_iterations as f64).clamp(0.0, 1.0).sqrt();
+                    hsv_to_rgb(arg.to_degrees(), 0.85, value)
                 }
+                None => (0, 0, 0),
+            };
 
-                if should_blue {
-                    for v in visited.iter().take(ITERATIONS_B) {
-                        let pixel = get_pixel(&v);
+            image.put_pixel(x as u32, y as u32, image::Rgb([r, g, b]));
+        }
+    }
 
-                        if let Some(pixel) = pixel {
-                            b[pixel.y][pixel.x].fetch_add(1, Relaxed);
-                        }
-                    }
-                }
+    image
+}
 
-                break;
-            }
+/// The Metropolis contribution function: how many of `visited`'s points
+/// land inside the view rectangle, summed across the R/G/B channels'
+/// iteration caps (a point visited early counts towards all three, one
+/// visited past `ITERATIONS_B` towards red and green, and one visited only
+/// past `ITERATIONS_G` towards just red).
+fn brightness(visited: &[Complex], viewport: &Viewport) -> f64 {
+    let in_view = |take: usize| -> f64 {
+        visited
+            .iter()
+            .take(take)
+            .filter(|v| viewport.to_pixel(v).is_some())
+            .count() as f64
+    };
+
+    in_view(visited.len()) + in_view(ITERATIONS_G) + in_view(ITERATIONS_B)
+}
+
+/// Splats a full escaped orbit into the three channels, respecting each
+/// channel's iteration cap, all at the same `sample_weight`.
+fn splat_orbit(
+    r: &mut BuddhabrotChannel,
+    g: &mut BuddhabrotChannel,
+    b: &mut BuddhabrotChannel,
+    visited: &[Complex],
+    filter: &Filter,
+    sample_weight: f64,
+    viewport: &Viewport,
+) {
+    for v in visited.iter() {
+        if let Some((px, py)) = viewport.to_pixel(v) {
+            splat(r, px, py, filter, sample_weight);
+        }
+    }
+
+    for v in visited.iter().take(ITERATIONS_G) {
+        if let Some((px, py)) = viewport.to_pixel(v) {
+            splat(g, px, py, filter, sample_weight);
+        }
+    }
+
+    for v in visited.iter().take(ITERATIONS_B) {
+        if let Some((px, py)) = viewport.to_pixel(v) {
+            splat(b, px, py, filter, sample_weight);
         }
     }
 }
 
-trait Normalize {
-    fn normalize(&self);
+fn generate_uniform(
+    r: &mut BuddhabrotChannel,
+    g: &mut BuddhabrotChannel,
+    b: &mut BuddhabrotChannel,
+    filter: &Filter,
+    rng: &mut impl Rng,
+    count: usize,
+    viewport: &Viewport,
+) {
+    for _ in 0..count {
+        let c = uniform_sample(rng, viewport);
+
+        if let Some(visited) = escape_orbit(&c) {
+            splat_orbit(r, g, b, &visited, filter, 1.0, viewport);
+        }
+    }
 }
 
-impl Normalize for BuddhabrotChannel {
-    fn normalize(self: &BuddhabrotChannel) {
-        let mut max = 0;
+/// Runs `samples` uniform trials and returns the brightest one as the
+/// chain's starting state, so the Metropolis walk doesn't spend its first
+/// mutations wandering out of an unlucky dark seed.
+fn warm_up_metropolis(
+    rng: &mut impl Rng,
+    samples: usize,
+    viewport: &Viewport,
+) -> (Complex, f64, Vec<Complex>) {
+    let mut best: Option<(Complex, f64, Vec<Complex>)> = None;
 
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let value = self[y][x].load(Relaxed);
-                if value > max {
-                    max = value;
-                }
+    for _ in 0..samples {
+        let c = uniform_sample(rng, viewport);
+
+        if let Some(visited) = escape_orbit(&c) {
+            let b = brightness(&visited, viewport);
+
+            if best.as_ref().is_none_or(|(_, best_b, _)| b > *best_b) {
+                best = Some((c, b, visited));
             }
         }
+    }
+
+    best.unwrap_or((Complex { re: 0.0, im: 0.0 }, 0.0, Vec::new()))
+}
+
+fn generate_metropolis(
+    r: &mut BuddhabrotChannel,
+    g: &mut BuddhabrotChannel,
+    b: &mut BuddhabrotChannel,
+    filter: &Filter,
+    rng: &mut impl Rng,
+    count: usize,
+    viewport: &Viewport,
+) {
+    let warm_up_samples = METROPOLIS_WARM_UP_SAMPLES.min(count.max(1));
+    let (mut c_curr, mut b_curr, mut visited_curr) =
+        warm_up_metropolis(rng, warm_up_samples, viewport);
 
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let value = self[y][x].load(Relaxed);
-                self[y][x].store(((value as f64 / max as f64) * 255.0) as u16, Relaxed);
+    // Running average brightness across every proposal so far (accepted or
+    // not, small mutations included), seeded with the warm-up's brightest
+    // sample -- this rescales deposits towards the absolute scale uniform
+    // sampling would produce, though mutations around bright seeds pull the
+    // average above the uniform mean; the per-deposit division by `b_curr`
+    // below is what actually cancels the chain's `b(c)` stationary density.
+    let mut brightness_sum = b_curr;
+    let mut proposals = 1usize;
+
+    for _ in 0..count {
+        let c_new = if rng.gen::<f64>() < METROPOLIS_P_LARGE {
+            uniform_sample(rng, viewport)
+        } else {
+            Complex {
+                re: c_curr.re + sample_gaussian(rng, METROPOLIS_MUTATION_SIGMA),
+                im: c_curr.im + sample_gaussian(rng, METROPOLIS_MUTATION_SIGMA),
             }
+        };
+
+        let (b_new, visited_new) = match escape_orbit(&c_new) {
+            Some(visited) => (brightness(&visited, viewport), visited),
+            None => (0.0, Vec::new()),
+        };
+
+        proposals += 1;
+        brightness_sum += b_new;
+
+        let accept = if b_curr <= 0.0 {
+            b_new > 0.0
+        } else {
+            rng.gen::<f64>() < (b_new / b_curr).min(1.0)
+        };
+
+        if accept {
+            c_curr = c_new;
+            b_curr = b_new;
+            visited_curr = visited_new;
+        }
+
+        if b_curr > 0.0 {
+            let running_mean = brightness_sum / proposals as f64;
+            // The chain visits c with stationary density proportional to
+            // b(c), and depositing its orbit adds mass proportional to
+            // b(c) again, so every deposit must be divided by b_curr (not
+            // just scaled by the global mean) to cancel that extra factor
+            // and recover the uniform-sampling target density.
+            splat_orbit(
+                r,
+                g,
+                b,
+                &visited_curr,
+                filter,
+                running_mean / b_curr,
+                viewport,
+            );
         }
     }
 }
 
-fn generate_channel() -> (Arc<BuddhabrotChannel>, Arc<BuddhabrotChannel>, Arc<BuddhabrotChannel>) {
-    let num_cores = 32;
+#[allow(clippy::too_many_arguments)]
+fn generate_batch(
+    r: &mut BuddhabrotChannel,
+    g: &mut BuddhabrotChannel,
+    b: &mut BuddhabrotChannel,
+    filter: &Filter,
+    mode: SamplingMode,
+    rng: &mut impl Rng,
+    count: usize,
+    viewport: &Viewport,
+) {
+    match mode {
+        SamplingMode::Uniform => generate_uniform(r, g, b, filter, rng, count, viewport),
+        SamplingMode::Metropolis => generate_metropolis(r, g, b, filter, rng, count, viewport),
+    }
+}
 
-    let mut threads = vec![];
+/// Samples are split into fixed-size batches and handed out through a
+/// rayon work-stealing pool instead of splitting `POINTS` evenly across a
+/// hardcoded thread count -- so total work is `POINTS` regardless of how
+/// many threads end up running, faster workers naturally pull more
+/// batches, and a batch's RNG is seeded from its own index rather than
+/// thread identity, so the result is reproducible for a given `seed`
+/// irrespective of scheduling.
+const BATCH_SIZE: usize = 64 * 1024;
 
-    let mut r: BuddhabrotChannel = Vec::with_capacity(SCREEN_HEIGHT);
-    let mut g: BuddhabrotChannel = Vec::with_capacity(SCREEN_HEIGHT);
-    let mut b: BuddhabrotChannel = Vec::with_capacity(SCREEN_HEIGHT);
+#[allow(clippy::too_many_arguments)]
+fn generate_channel(
+    mode: SamplingMode,
+    filter: Filter,
+    num_threads: Option<usize>,
+    seed: u64,
+    viewport: Viewport,
+) -> (BuddhabrotChannel, BuddhabrotChannel, BuddhabrotChannel) {
+    let batch_count = POINTS.div_ceil(BATCH_SIZE);
+    let batches_done = AtomicUsize::new(0);
+    let thread_count = num_threads.unwrap_or_else(num_cpus::get);
 
-    for _ in 0..SCREEN_HEIGHT {
-        let mut row_r = Vec::with_capacity(SCREEN_WIDTH);
-        let mut row_g = Vec::with_capacity(SCREEN_WIDTH);
-        let mut row_b = Vec::with_capacity(SCREEN_WIDTH);
-        for _ in 0..SCREEN_WIDTH {
-            row_r.push(AtomicU16::new(0));
-            row_g.push(AtomicU16::new(0));
-            row_b.push(AtomicU16::new(0));
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .build()
+        .unwrap();
+
+    // `with_min_len` keeps rayon from bisecting the batch range down to
+    // single batches; each split (one per worker, in the common case) folds
+    // its whole run of batches into one private film shard instead of
+    // allocating and merging a fresh full-screen film per batch.
+    let min_len = batch_count.div_ceil(thread_count).max(1);
+
+    pool.install(|| {
+        (0..batch_count)
+            .into_par_iter()
+            .with_min_len(min_len)
+            .fold(
+                || (new_channel(), new_channel(), new_channel()),
+                |mut shard, batch_index| {
+                    let batch_points = BATCH_SIZE.min(POINTS - batch_index * BATCH_SIZE);
+
+                    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(batch_index as u64));
+
+                    generate_batch(
+                        &mut shard.0,
+                        &mut shard.1,
+                        &mut shard.2,
+                        &filter,
+                        mode,
+                        &mut rng,
+                        batch_points,
+                        &viewport,
+                    );
+
+                    let done = batches_done.fetch_add(1, Relaxed) + 1;
+                    println!(
+                        "{:.2}% Done ({done}/{batch_count} batches)",
+                        (done as f64 / batch_count as f64) * 100.0
+                    );
+
+                    shard
+                },
+            )
+            .reduce(
+                || (new_channel(), new_channel(), new_channel()),
+                |mut acc, item| {
+                    merge_channel(&mut acc.0, &item.0);
+                    merge_channel(&mut acc.1, &item.1);
+                    merge_channel(&mut acc.2, &item.2);
+                    acc
+                },
+            )
+    })
+}
+
+/// Which renderer `main` runs: the additive orbit-density Buddhabrot, or
+/// the escape-angle domain-coloring companion view.
+#[derive(Debug, Copy, Clone)]
+enum RenderMode {
+    Buddhabrot,
+    DomainColor,
+}
+
+const DEFAULT_GAUSSIAN_ALPHA: f64 = 2.0;
+const DEFAULT_MITCHELL_B: f64 = 1.0 / 3.0;
+const DEFAULT_MITCHELL_C: f64 = 1.0 / 3.0;
+const DEFAULT_GAMMA: f64 = 2.2;
+const DEFAULT_CLIP_PERCENTILE: f64 = 99.5;
+
+/// Framing and mode parameters accepted on the command line, so neither the
+/// rendered region nor the renderer requires a recompile to change.
+struct CliArgs {
+    center: Complex,
+    zoom: f64,
+    rotation_degrees: f64,
+    mode: RenderMode,
+    filter: FilterKind,
+    sampling: SamplingMode,
+    exposure: [f64; 3],
+    curve: ResponseCurve,
+    clip_percentile: Option<f64>,
+    threads: Option<usize>,
+    seed: u64,
+}
+
+impl Default for CliArgs {
+    fn default() -> CliArgs {
+        CliArgs {
+            center: Complex {
+                re: -DEFAULT_PAN_RIGHT,
+                im: 0.0,
+            },
+            zoom: 1.0,
+            rotation_degrees: 0.0,
+            mode: RenderMode::Buddhabrot,
+            filter: FilterKind::MitchellNetravali {
+                b: DEFAULT_MITCHELL_B,
+                c: DEFAULT_MITCHELL_C,
+            },
+            sampling: SamplingMode::Metropolis,
+            exposure: [1.0, 1.0, 1.0],
+            curve: ResponseCurve::Gamma(DEFAULT_GAMMA),
+            clip_percentile: Some(DEFAULT_CLIP_PERCENTILE),
+            threads: None,
+            seed: 0,
         }
-        r.push(row_r);
-        g.push(row_g);
-        b.push(row_b);
     }
+}
 
-    let r = Arc::new(r);
-    let g = Arc::new(g);
-    let b = Arc::new(b);
+fn parse_f64_flag(flag: &str, value: &str) -> f64 {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("{flag} expects a numeric value, got `{value}`"))
+}
 
-    for _i in 0..num_cores {
-        let r = Arc::clone(&r);
-        let g = Arc::clone(&g);
-        let b = Arc::clone(&b);
-        threads.push(thread::spawn(move || {
-            generate(&r, &g, &b);
-        }));
-    }
+fn parse_int_flag<T: std::str::FromStr>(flag: &str, value: &str) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("{flag} expects an integer value, got `{value}`"))
+}
 
-    threads.into_iter().for_each(|t| t.join().unwrap());
+/// Parses `--center-re`, `--center-im`, `--zoom`, `--rotation` (degrees),
+/// `--mode` (`buddhabrot` or `domain-color`), `--filter` (`box`, `gaussian`,
+/// or `mitchell`) with its kernel-specific `--filter-alpha`/`--filter-b`/
+/// `--filter-c` overrides, `--sampling` (`uniform` or `metropolis`), the
+/// per-channel `--exposure-r`/`--exposure-g`/`--exposure-b`, `--curve`
+/// (`linear`, `gamma`, `log`, or `reinhard`, with a `--curve-param` for
+/// gamma's `gamma`, applied as `v^(1/gamma)`, or log's `k`), `--clip-percentile` (or `none` for the
+/// absolute max), `--threads` (defaults to `num_cpus::get()`), and `--seed`
+/// (for reproducible output) out of `std::env::args()`. A hand-rolled
+/// parser rather than a CLI crate, since there are only a handful of
+/// optional flags.
+fn parse_args() -> CliArgs {
+    let mut args = CliArgs::default();
+    let mut raw = std::env::args().skip(1);
 
-    println!("Normalizing red");
-    r.normalize();
-    println!("Normalizing green");
-    g.normalize();
-    println!("Normalizing blue");
-    b.normalize();
+    while let Some(flag) = raw.next() {
+        let value = raw
+            .next()
+            .unwrap_or_else(|| panic!("{flag} expects a value"));
 
+        match flag.as_str() {
+            "--center-re" => args.center.re = parse_f64_flag(&flag, &value),
+            "--center-im" => args.center.im = parse_f64_flag(&flag, &value),
+            "--zoom" => args.zoom = parse_f64_flag(&flag, &value),
+            "--rotation" => args.rotation_degrees = parse_f64_flag(&flag, &value),
+            "--mode" => {
+                args.mode = match value.as_str() {
+                    "buddhabrot" => RenderMode::Buddhabrot,
+                    "domain-color" => RenderMode::DomainColor,
+                    _ => panic!("--mode expects `buddhabrot` or `domain-color`, got `{value}`"),
+                }
+            }
+            "--filter" => {
+                args.filter = match value.as_str() {
+                    "box" => FilterKind::Box,
+                    "gaussian" => FilterKind::Gaussian {
+                        alpha: DEFAULT_GAUSSIAN_ALPHA,
+                    },
+                    "mitchell" => FilterKind::MitchellNetravali {
+                        b: DEFAULT_MITCHELL_B,
+                        c: DEFAULT_MITCHELL_C,
+                    },
+                    _ => panic!("--filter expects `box`, `gaussian`, or `mitchell`, got `{value}`"),
+                }
+            }
+            "--filter-alpha" => match &mut args.filter {
+                FilterKind::Gaussian { alpha } => *alpha = parse_f64_flag(&flag, &value),
+                _ => panic!("--filter-alpha only applies to --filter gaussian"),
+            },
+            "--filter-b" => match &mut args.filter {
+                FilterKind::MitchellNetravali { b, .. } => *b = parse_f64_flag(&flag, &value),
+                _ => panic!("--filter-b only applies to --filter mitchell"),
+            },
+            "--filter-c" => match &mut args.filter {
+                FilterKind::MitchellNetravali { c, .. } => *c = parse_f64_flag(&flag, &value),
+                _ => panic!("--filter-c only applies to --filter mitchell"),
+            },
+            "--sampling" => {
+                args.sampling = match value.as_str() {
+                    "uniform" => SamplingMode::Uniform,
+                    "metropolis" => SamplingMode::Metropolis,
+                    _ => panic!("--sampling expects `uniform` or `metropolis`, got `{value}`"),
+                }
+            }
+            "--exposure-r" => args.exposure[0] = parse_f64_flag(&flag, &value),
+            "--exposure-g" => args.exposure[1] = parse_f64_flag(&flag, &value),
+            "--exposure-b" => args.exposure[2] = parse_f64_flag(&flag, &value),
+            "--curve" => {
+                args.curve = match value.as_str() {
+                    "linear" => ResponseCurve::Linear,
+                    "gamma" => ResponseCurve::Gamma(DEFAULT_GAMMA),
+                    "log" => ResponseCurve::Logarithmic(1.0),
+                    "reinhard" => ResponseCurve::Reinhard,
+                    _ => panic!(
+                        "--curve expects `linear`, `gamma`, `log`, or `reinhard`, got `{value}`"
+                    ),
+                }
+            }
+            "--curve-param" => match &mut args.curve {
+                ResponseCurve::Gamma(gamma) => *gamma = parse_f64_flag(&flag, &value),
+                ResponseCurve::Logarithmic(k) => *k = parse_f64_flag(&flag, &value),
+                _ => panic!("--curve-param only applies to --curve gamma or log"),
+            },
+            "--clip-percentile" => {
+                args.clip_percentile = match value.as_str() {
+                    "none" => None,
+                    _ => Some(parse_f64_flag(&flag, &value)),
+                }
+            }
+            "--threads" => args.threads = Some(parse_int_flag(&flag, &value)),
+            "--seed" => args.seed = parse_int_flag(&flag, &value),
+            _ => panic!("unknown flag `{flag}`"),
+        }
+    }
 
-    (r, g, b)
+    args
 }
 
 fn main() {
-    let (r, g, b) = generate_channel();
+    let args = parse_args();
 
-    pixels_to_png(&r, &g, &b).unwrap();
+    let viewport = Viewport::from_center_zoom(args.center, args.zoom, SCREEN_WIDTH, SCREEN_HEIGHT)
+        .rotated(args.rotation_degrees.to_radians());
+
+    match args.mode {
+        RenderMode::Buddhabrot => {
+            let filter = Filter::from_kind(args.filter);
+            let (r, g, b) =
+                generate_channel(args.sampling, filter, args.threads, args.seed, viewport);
+
+            let pipeline = OutputPipeline::new(args.exposure, args.curve, args.clip_percentile);
+
+            let mapped = pipeline.map(&r, &g, &b);
+
+            write_images(&mapped).unwrap();
+        }
+        RenderMode::DomainColor => {
+            render_domain_color(viewport, DOMAIN_COLOR_MAX_ITERATIONS)
+                .save("domain_color.png")
+                .unwrap();
+        }
+    }
 }